@@ -0,0 +1,51 @@
+use std::ptr;
+
+use esp_idf_hal::reset::ResetReason;
+use esp_idf_sys::{
+    esp, esp_get_free_heap_size, esp_get_minimum_free_heap_size, esp_timer_get_time,
+    esp_wifi_sta_get_ap_info, uxTaskGetStackHighWaterMark, wifi_ap_record_t,
+};
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct Diagnostics {
+    pub uptime_ms: u64,
+    pub reset_reason: String,
+    pub free_heap: u32,
+    pub min_free_heap: u32,
+    // Minimum free stack ever seen, in bytes
+    pub http_task_stack_free: u32,
+    pub send_task_stack_free: u32,
+    // Not available when the station is not connected
+    pub wifi_rssi: Option<i8>,
+}
+
+impl Diagnostics {
+    // Must be called from the http server task
+    pub fn collect(send_task_stack_free: u32) -> Self {
+        Self {
+            uptime_ms: uptime_ms(),
+            reset_reason: format!("{:?}", ResetReason::get()),
+            free_heap: unsafe { esp_get_free_heap_size() },
+            min_free_heap: unsafe { esp_get_minimum_free_heap_size() },
+            http_task_stack_free: stack_free(),
+            send_task_stack_free,
+            wifi_rssi: wifi_rssi(),
+        }
+    }
+}
+
+pub fn uptime_ms() -> u64 {
+    (unsafe { esp_timer_get_time() } / 1000) as u64
+}
+
+// Minimum free stack of the calling task
+pub fn stack_free() -> u32 {
+    unsafe { uxTaskGetStackHighWaterMark(ptr::null_mut()) }
+}
+
+fn wifi_rssi() -> Option<i8> {
+    let mut ap_info: wifi_ap_record_t = Default::default();
+    esp!(unsafe { esp_wifi_sta_get_ap_info(&mut ap_info) }).ok()?;
+    Some(ap_info.rssi)
+}
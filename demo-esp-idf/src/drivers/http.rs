@@ -1,19 +1,42 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 use std::time::Instant;
 use std::{thread::sleep, time::Duration};
 
-use embedded_svc::http::Method;
-use embedded_svc::io::Write;
+use anyhow::anyhow;
+use embedded_svc::http::server::{HandlerResult, Request};
+use embedded_svc::http::{Headers, Method};
+use embedded_svc::io::{Read, Write};
+use embedded_svc::utils::io::try_read_full;
 use embedded_svc::ws::FrameType;
 use esp_idf_svc::http::server::ws::EspHttpWsDetachedSender;
-use esp_idf_svc::http::server::{Configuration, EspHttpServer};
+use esp_idf_svc::http::server::{Configuration, EspHttpConnection, EspHttpServer};
 use esp_idf_sys::EspError;
-use demo::app::SystemState;
+use demo::app::{OtaStatus, SystemState, Vec3};
+use demo::hal::storage::Storage;
+use demo::settings::{Settings, StatePushSettings};
+use demo::svc::bus::Event;
+use demo::svc::http::{HttpAccess, HttpHandler, HttpMethod, HttpRequest};
+use demo::svc::log_throttle::LogThrottle;
+use log::Level;
+
+use crate::drivers::diagnostics::{stack_free, Diagnostics};
+use crate::drivers::openapi::{Access, ApiDoc};
+use crate::drivers::ota::EspOtaUpdater;
+use crate::drivers::storage::{EspStorage, SETTINGS_MAX_SIZE};
+
+const LOG_THROTTLE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Largest request body accepted by handlers added with add_handler
+const HANDLER_BODY_MAX_SIZE: usize = 1024;
 
 struct StateSender {
     ws: EspHttpWsDetachedSender,
+    // Received the state at least once
+    synced: bool,
 }
 
 #[derive(Clone)]
@@ -28,35 +51,43 @@ impl StateSenders {
         log::info!("detached sender created");
         if let Ok(mut senders) = self.0.lock() {
             log::info!("detached sender added");
-            senders.push_back(StateSender { ws });
+            senders.push_back(StateSender { ws, synced: false });
             log::info!("detached senders count: {}", senders.len());
         }
     }
 
-    fn send(&self, system_state: &SystemState) {
+    // When the state did not change, it is only sent to senders which never
+    // received it. Returns the number of senders which failed, or None when
+    // nothing was sent because the senders were busy.
+    fn send(&self, system_state: &SystemState, changed: bool) -> Option<usize> {
         let frame_type = FrameType::Binary(false);
 
         let json = serde_json::to_vec(&system_state).unwrap();
         let data = json.as_slice();
 
+        let mut err_count = 0;
+
         // try_lock is used because we want to avoid waiting for the lock to be
         // acquired and we accept to miss some transmission.
-        if let Ok(mut senders) = self.0.try_lock() {
-            let mut err_count = 0;
+        let mut senders = self.0.try_lock().ok()?;
 
-            // FIXME Clean up closed
-            let senders: &mut VecDeque<StateSender> = &mut senders;
+        // Closed senders are removed by cleanup_closed, they are only skipped
+        // here
+        let senders: &mut VecDeque<StateSender> = &mut senders;
 
-            for sender in senders.iter_mut().filter(|x| !x.ws.is_closed()) {
-                if sender.ws.send(frame_type, data).is_err() {
-                    err_count += 1;
-                }
-            }
+        let receivers = senders
+            .iter_mut()
+            .filter(|x| !x.ws.is_closed() && (changed || !x.synced));
 
-            if err_count > 0 {
-                log::error!("error sending status");
+        for sender in receivers {
+            // A failed sender gets the state again even if it does not change
+            sender.synced = sender.ws.send(frame_type, data).is_ok();
+            if !sender.synced {
+                err_count += 1;
             }
         }
+
+        Some(err_count)
     }
 
     fn cleanup_closed(&self) {
@@ -72,45 +103,45 @@ impl StateSenders {
 }
 
 pub struct HttpServer {
-    #[allow(dead_code)]
-    esp_http_server: EspHttpServer,
-    app_state: Arc<Mutex<SystemState>>,
+    esp_http_server: RefCell<EspHttpServer>,
+    ctx: HandlerContext,
     #[allow(dead_code)]
     send_task: JoinHandle<()>,
 }
 
-fn add_handlers(server: &mut EspHttpServer) -> anyhow::Result<StateSenders> {
+// State shared by the http server with its handlers
+#[derive(Clone)]
+struct HandlerContext {
+    app_state: Arc<Mutex<SystemState>>,
+    storage: EspStorage,
+    ota: EspOtaUpdater,
+    settings_update: Arc<Mutex<Option<Settings>>>,
+    // Published on the app bus, which then logs them
+    events: Arc<Mutex<Vec<Event>>>,
+    state_push: Arc<Mutex<StatePushSettings>>,
+    send_task_stack_free: Arc<AtomicU32>,
+    api_doc: ApiDoc,
+}
+
+fn add_handlers(server: &mut EspHttpServer, ctx: &HandlerContext) -> anyhow::Result<StateSenders> {
     let state_senders = StateSenders::new();
     let state_senders_copy = state_senders.clone();
 
     server.fn_handler("/", Method::Get, |request| {
-        let mut response = request.into_ok_response()?;
-        response.write_all(index_html())?;
-        Ok(())
+        send_asset(request, "text/html", Some(index_html()), index_html_gz())
     })?;
 
     server.fn_handler("/style.css", Method::Get, |request| {
-        let headers = [("Content-Type", "text/css")];
-        let mut response = request.into_response(200, None, &headers)?;
-        response.write_all(ui_css())?;
-        Ok(())
+        send_asset(request, "text/css", Some(ui_css()), ui_css_gz())
     })?;
 
     server.fn_handler("/demo-ui.js", Method::Get, |request| {
-        let headers = [("Content-Type", "application/javascript")];
-        let mut response = request.into_response(200, None, &headers)?;
-        response.write_all(ui_js())?;
-        Ok(())
+        send_asset(request, "application/javascript", Some(ui_js()), ui_js_gz())
     })?;
 
+    // Only the compressed wasm is embedded, to save flash
     server.fn_handler("/demo-ui_bg.wasm", Method::Get, |request| {
-        let headers = [
-            ("Content-Type", "application/wasm"),
-            ("Content-Encoding", "gzip"),
-        ];
-        let mut response = request.into_response(200, None, &headers)?;
-        response.write_all(ui_wasm())?;
-        Ok(())
+        send_asset(request, "application/wasm", None, ui_wasm())
     })?;
 
     // TODO this file name may change
@@ -147,67 +178,510 @@ fn add_handlers(server: &mut EspHttpServer) -> anyhow::Result<StateSenders> {
         Ok(())
     })?;
 
+    let mut api = ApiRoutes {
+        server,
+        doc: &ctx.api_doc,
+        storage: &ctx.storage,
+    };
+
+    // The backup is just another name for the stored settings
+    for uri in ["/config", "/api/config/backup"] {
+        let ctx_copy = ctx.clone();
+
+        api.protected(Method::Get, uri, "Stored settings", move |request| {
+            send_settings(request, &ctx_copy.storage)
+        })?;
+    }
+
+    let ctx_copy = ctx.clone();
+
+    api.protected(
+        Method::Put,
+        "/config",
+        "Store and apply settings",
+        move |mut request| {
+            let settings = match read_settings(&mut request)? {
+                Ok(settings) => settings,
+                Err((status, message)) => return respond_error(request, status, &message),
+            };
+
+            let previous = ctx_copy.storage.load_settings()?.unwrap_or_default();
+            ctx_copy.storage.store_settings(&settings)?;
+            log::info!("Settings stored");
+
+            let mut response = request.into_ok_response()?;
+
+            // Wi-Fi and MQTT are only configured at boot, everything else is
+            // applied while running
+            if settings.wifi != previous.wifi || settings.mqtt != previous.mqtt {
+                queue_event(&ctx_copy, Event::SettingsChanged);
+                response.write_all("Settings stored, restarting".as_bytes())?;
+                restart_later();
+            } else {
+                if let Ok(mut state_push) = ctx_copy.state_push.lock() {
+                    *state_push = settings.state_push.unwrap_or_default();
+                }
+                if let Ok(mut settings_update) = ctx_copy.settings_update.lock() {
+                    *settings_update = Some(settings);
+                }
+                response.write_all("Settings applied".as_bytes())?;
+            }
+
+            Ok(())
+        },
+    )?;
+
+    let ctx_copy = ctx.clone();
+
+    api.protected(
+        Method::Post,
+        "/api/config/restore",
+        "Store settings and restart",
+        move |mut request| {
+            let settings = match read_settings(&mut request)? {
+                Ok(settings) => settings,
+                Err((status, message)) => return respond_error(request, status, &message),
+            };
+
+            ctx_copy.storage.store_settings(&settings)?;
+            log::info!("Settings restored");
+            queue_event(&ctx_copy, Event::SettingsChanged);
+
+            let mut response = request.into_ok_response()?;
+            response.write_all("Settings restored, restarting".as_bytes())?;
+            restart_later();
+
+            Ok(())
+        },
+    )?;
+
+    let ctx_copy = ctx.clone();
+
+    api.public(
+        Method::Get,
+        "/diagnostics",
+        "Board diagnostics",
+        move |request| {
+            let send_task_stack_free = ctx_copy.send_task_stack_free.load(Ordering::Relaxed);
+            let json = serde_json::to_vec(&Diagnostics::collect(send_task_stack_free))?;
+            let headers = [("Content-Type", "application/json")];
+            let mut response = request.into_response(200, None, &headers)?;
+            response.write_all(&json)?;
+            Ok(())
+        },
+    )?;
+
+    let ctx_copy = ctx.clone();
+
+    api.protected(
+        Method::Post,
+        "/ota",
+        "Update firmware and restart",
+        move |mut request| {
+            let Some(size) = request.content_len() else {
+                return respond_error(request, 411, "Content length required");
+            };
+
+            let size = size as usize;
+
+            log::info!("OTA update started, {} bytes", size);
+
+            let set_status = |ota: OtaStatus| update_state(&ctx_copy.app_state, |x| x.ota = ota);
+
+            let result = ctx_copy.ota.update(&mut request, size, |written| {
+                set_status(OtaStatus::InProgress {
+                    written: written as u32,
+                    size: size as u32,
+                })
+            });
+
+            if let Err(err) = result {
+                log::error!("OTA update failed: {}", err);
+                set_status(OtaStatus::Failed);
+                return respond_error(request, 500, &err.to_string());
+            }
+
+            log::info!("OTA update completed");
+            set_status(OtaStatus::Completed);
+
+            let mut response = request.into_ok_response()?;
+            response.write_all("Firmware updated, restarting".as_bytes())?;
+            restart_later();
+
+            Ok(())
+        },
+    )?;
+
+    let ctx_copy = ctx.clone();
+
+    api.public(
+        Method::Get,
+        "/openapi.json",
+        "This description",
+        move |request| {
+            let json = ctx_copy.api_doc.to_json()?;
+            let headers = [("Content-Type", "application/json")];
+            let mut response = request.into_response(200, None, &headers)?;
+            response.write_all(&json)?;
+            Ok(())
+        },
+    )?;
+
     Ok(state_senders)
 }
 
-fn spawn_send_task(state_senders: StateSenders, state: Arc<Mutex<SystemState>>) -> JoinHandle<()> {
-    const TASK_WAKEUP_PERIOD: Duration = Duration::from_millis(250);
+// Registers handlers which are part of the documented API
+struct ApiRoutes<'a> {
+    server: &'a mut EspHttpServer,
+    doc: &'a ApiDoc,
+    storage: &'a EspStorage,
+}
+
+impl ApiRoutes<'_> {
+    fn public<F>(&mut self, method: Method, uri: &str, summary: &str, f: F) -> Result<(), EspError>
+    where
+        F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> HandlerResult + Send + 'static,
+    {
+        self.add(method, uri, summary, Access::Public, f)
+    }
+
+    // The handler only runs when the request carries the API token
+    fn protected<F>(
+        &mut self,
+        method: Method,
+        uri: &str,
+        summary: &str,
+        f: F,
+    ) -> Result<(), EspError>
+    where
+        F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> HandlerResult + Send + 'static,
+    {
+        self.add(method, uri, summary, Access::Token, f)
+    }
+
+    fn add<F>(
+        &mut self,
+        method: Method,
+        uri: &str,
+        summary: &str,
+        access: Access,
+        f: F,
+    ) -> Result<(), EspError>
+    where
+        F: for<'r> Fn(Request<&mut EspHttpConnection<'r>>) -> HandlerResult + Send + 'static,
+    {
+        self.doc.add(method, uri, summary, access);
+
+        let storage = self.storage.clone();
+
+        self.server.fn_handler(uri, method, move |request| {
+            if access == Access::Token && !is_authorized(&request, &storage)? {
+                return respond_error(request, 401, "Unauthorized");
+            }
+
+            f(request)
+        })?;
+
+        Ok(())
+    }
+}
+
+fn send_settings(request: Request<&mut EspHttpConnection>, storage: &EspStorage) -> HandlerResult {
+    let settings = storage.load_settings()?.unwrap_or_default();
+    let json = serde_json::to_vec(&settings)?;
+    let headers = [("Content-Type", "application/json")];
+    let mut response = request.into_response(200, None, &headers)?;
+    response.write_all(&json)?;
+    Ok(())
+}
+
+// Assets are compressed at build time, the compressed version is sent to
+// clients accepting it
+fn send_asset(
+    request: Request<&mut EspHttpConnection>,
+    content_type: &str,
+    plain: Option<&[u8]>,
+    gzipped: &[u8],
+) -> HandlerResult {
+    let (data, gzip) = match (accepts_gzip(&request), plain) {
+        (true, _) => (gzipped, true),
+        (false, Some(plain)) => (plain, false),
+        (false, None) => return respond_error(request, 406, "gzip encoding required"),
+    };
+
+    // Content-Encoding is left out for uncompressed data
+    let headers = [
+        ("Content-Type", content_type),
+        ("Vary", "Accept-Encoding"),
+        ("Content-Encoding", "gzip"),
+    ];
+    let headers = if gzip { &headers[..] } else { &headers[..2] };
+    let mut response = request.into_response(200, None, headers)?;
+    response.write_all(data)?;
+    Ok(())
+}
+
+fn accepts_gzip(request: &Request<&mut EspHttpConnection>) -> bool {
+    let Some(accept_encoding) = request.header("Accept-Encoding") else {
+        return false;
+    };
+
+    // A zero quality value, as in "gzip;q=0", refuses the encoding
+    accept_encoding.split(',').any(|x| {
+        let mut params = x.split(';').map(str::trim);
+        let is_gzip = params.next() == Some("gzip");
+        let quality = params
+            .find_map(|param| param.strip_prefix("q="))
+            .map_or(1.0, |q| q.parse::<f32>().unwrap_or(0.0));
+        is_gzip && quality > 0.0
+    })
+}
+
+// Expects the API token of the stored settings as a bearer token
+fn is_authorized(
+    request: &Request<&mut EspHttpConnection>,
+    storage: &EspStorage,
+) -> anyhow::Result<bool> {
+    let settings = storage.load_settings()?.unwrap_or_default();
+    let token = request
+        .header("Authorization")
+        .and_then(|x| x.strip_prefix("Bearer "));
+    Ok(settings.is_authorized(token))
+}
+
+fn respond_error(
+    request: Request<&mut EspHttpConnection>,
+    status: u16,
+    message: &str,
+) -> HandlerResult {
+    let mut response = request.into_status_response(status)?;
+    response.write_all(message.as_bytes())?;
+    Ok(())
+}
+
+// Returns None when the body does not fit in the buffer
+fn read_body<'b, R>(request: &mut R, buf: &'b mut [u8]) -> Result<Option<&'b [u8]>, R::Error>
+where
+    R: Read + Headers,
+{
+    let len = request.content_len().unwrap_or(0) as usize;
+
+    if len > buf.len() {
+        return Ok(None);
+    }
+
+    let len = try_read_full(request, &mut buf[..len]).map_err(|(err, _)| err)?;
+
+    Ok(Some(&buf[..len]))
+}
+
+// The error is the status and message to respond with
+fn read_settings(
+    request: &mut Request<&mut EspHttpConnection>,
+) -> anyhow::Result<Result<Settings, (u16, String)>> {
+    let mut buf = [0u8; SETTINGS_MAX_SIZE];
+
+    Ok(match read_body(request, &mut buf)?.map(parse_settings) {
+        Some(Ok(settings)) => Ok(settings),
+        Some(Err(err)) => Err((400, err.to_string())),
+        None => Err((413, "Settings too large".to_owned())),
+    })
+}
+
+fn parse_settings(data: &[u8]) -> anyhow::Result<Settings> {
+    let settings: Settings = serde_json::from_slice(data)?;
+    settings
+        .validate()
+        .map_err(|err| anyhow!("Invalid settings: {:?}", err))?;
+    Ok(settings)
+}
+
+fn queue_event(ctx: &HandlerContext, event: Event) {
+    if let Ok(mut events) = ctx.events.lock() {
+        events.push(event);
+    }
+}
+
+// Give the http server the time to send the response before restarting
+fn restart_later() {
+    std::thread::spawn(|| {
+        sleep(Duration::from_secs(1));
+        esp_idf_hal::reset::restart();
+    });
+}
+
+fn spawn_send_task(state_senders: StateSenders, ctx: &HandlerContext) -> JoinHandle<()> {
+    let state = ctx.app_state.clone();
+    let state_push = ctx.state_push.clone();
+    let stack_free_out = ctx.send_task_stack_free.clone();
 
     std::thread::Builder::new()
         .stack_size(64 * 1024)
-        .spawn(move || loop {
-            let start = Instant::now();
+        .spawn(move || {
+            let mut send_error_log = LogThrottle::new(LOG_THROTTLE_INTERVAL);
+            let mut no_delay_log = LogThrottle::new(LOG_THROTTLE_INTERVAL);
+            let mut config = StatePushSettings::default();
+            let mut last_sent = None;
 
-            let next_wakeup = Instant::now() + TASK_WAKEUP_PERIOD;
+            loop {
+                let start = Instant::now();
 
-            state_senders.cleanup_closed();
+                if let Ok(x) = state_push.try_lock() {
+                    config = *x;
+                }
 
-            // Instead of keeping the mutex locked until the state is sent, we get
-            // a copy of the state and send it asynchronously.
-            if let Ok(state) = state.try_lock().map(|x| x.clone()) {
-                state_senders.send(&state);
-            }
+                let next_wakeup = Instant::now() + config.interval();
 
-            log::trace!("ws update took {}ms", (Instant::now() - start).as_millis());
+                state_senders.cleanup_closed();
 
-            // Ensure this task is not spinning
-            if let Some(delay) = next_wakeup.checked_duration_since(Instant::now()) {
-                sleep(delay);
-            } else {
-                log::error!("no delay");
+                // Instead of keeping the mutex locked until the state is sent, we get
+                // a copy of the state and send it asynchronously.
+                if let Ok(state) = state.try_lock().map(|x| *x) {
+                    let changed = !config.only_on_change || last_sent != Some(state);
+
+                    // A missed transmission is retried with the next update
+                    if let Some(err_count) = state_senders.send(&state, changed) {
+                        if err_count > 0 {
+                            send_error_log.log(Level::Error, format_args!("error sending status"));
+                        }
+                        last_sent = Some(state);
+                    }
+                }
+
+                log::trace!("ws update took {}ms", (Instant::now() - start).as_millis());
+
+                stack_free_out.store(stack_free(), Ordering::Relaxed);
+
+                // Ensure this task is not spinning
+                if let Some(delay) = next_wakeup.checked_duration_since(Instant::now()) {
+                    sleep(delay);
+                } else {
+                    no_delay_log.log(Level::Error, format_args!("no delay"));
+                }
             }
         })
         .unwrap()
 }
 
 impl HttpServer {
-    pub fn new() -> anyhow::Result<Self> {
-        let conf = Configuration::default();
+    pub fn new(storage: EspStorage, ota: EspOtaUpdater) -> anyhow::Result<Self> {
+        // Every WebSocket client keeps a socket open, leave room for a few
+        // of them along with the page requests. The server needs 3 more for
+        // itself, out of the 16 lwIP sockets set in sdkconfig.defaults, which
+        // leaves some for MQTT, the webhook and SNTP.
+        let conf = Configuration {
+            max_open_sockets: 7,
+            ..Default::default()
+        };
         let mut esp_http_server = EspHttpServer::new(&conf)?;
-        let app_state = Arc::new(Mutex::new(Default::default()));
-        let state_senders = add_handlers(&mut esp_http_server)?;
-
-        let send_task = spawn_send_task(state_senders.clone(), app_state.clone());
+        let app_state = SystemState {
+            ota: ota.boot_status(),
+            ..Default::default()
+        };
+        let settings = storage.load_settings().ok().flatten().unwrap_or_default();
+        let ctx = HandlerContext {
+            app_state: Arc::new(Mutex::new(app_state)),
+            storage,
+            ota,
+            settings_update: Arc::new(Mutex::new(None)),
+            events: Arc::new(Mutex::new(Vec::new())),
+            state_push: Arc::new(Mutex::new(settings.state_push.unwrap_or_default())),
+            send_task_stack_free: Arc::new(AtomicU32::new(0)),
+            api_doc: ApiDoc::default(),
+        };
+        let state_senders = add_handlers(&mut esp_http_server, &ctx)?;
+
+        let send_task = spawn_send_task(state_senders.clone(), &ctx);
 
         Ok(HttpServer {
-            esp_http_server,
-            app_state,
+            esp_http_server: RefCell::new(esp_http_server),
+            ctx,
             send_task,
         })
     }
 }
 
+impl HttpServer {
+    fn update_state(&self, f: impl FnOnce(&mut SystemState)) {
+        update_state(&self.ctx.app_state, f);
+    }
+}
+
+fn update_state(app_state: &Mutex<SystemState>, f: impl FnOnce(&mut SystemState)) {
+    // try_lock is used because we want to avoid waiting for the lock to be
+    // acquired and we accept to miss some update.
+    app_state.try_lock().as_mut().map(|x| f(x)).ok();
+}
+
 impl demo::svc::HttpServer for HttpServer {
-    fn set_system_state(&self, state: &SystemState) {
-        // try_lock is used because we want to avoid waiting for the lock to be
-        // acquired and we accept to miss some update.
-        self.app_state
-            .try_lock()
-            .as_mut()
-            .map(|x| {
-                **x = state.clone();
-            })
-            .ok();
+    fn set_counter(&self, counter: u32) {
+        self.update_state(|x| x.counter = counter);
+    }
+
+    fn set_acc(&self, acc: &Vec3) {
+        self.update_state(|x| x.acc = *acc);
+    }
+
+    fn ota_status(&self) -> OtaStatus {
+        // Waits for the lock, a missed read would report a wrong status
+        self.ctx.app_state.lock().map(|x| x.ota).unwrap_or_default()
+    }
+
+    fn take_settings_update(&self) -> Option<Settings> {
+        self.ctx.settings_update.try_lock().ok()?.take()
+    }
+
+    fn take_events(&self) -> Vec<Event> {
+        match self.ctx.events.try_lock() {
+            Ok(mut events) => std::mem::take(&mut *events),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    fn add_handler(
+        &self,
+        method: HttpMethod,
+        uri: &str,
+        access: HttpAccess,
+        summary: &str,
+        handler: HttpHandler,
+    ) -> anyhow::Result<()> {
+        let method = match method {
+            HttpMethod::Get => Method::Get,
+            HttpMethod::Post => Method::Post,
+            HttpMethod::Put => Method::Put,
+            HttpMethod::Delete => Method::Delete,
+        };
+
+        let mut server = self.esp_http_server.borrow_mut();
+
+        let mut api = ApiRoutes {
+            server: &mut server,
+            doc: &self.ctx.api_doc,
+            storage: &self.ctx.storage,
+        };
+
+        let access = match access {
+            HttpAccess::Public => Access::Public,
+            HttpAccess::Token => Access::Token,
+        };
+
+        api.add(method, uri, summary, access, move |mut request| {
+            let mut buf = [0u8; HANDLER_BODY_MAX_SIZE];
+
+            let Some(body) = read_body(&mut request, &mut buf)? else {
+                return respond_error(request, 413, "Request too large");
+            };
+
+            let response = handler(&HttpRequest { body });
+
+            let headers = [("Content-Type", response.content_type)];
+            let mut esp_response = request.into_response(response.status, None, &headers)?;
+            esp_response.write_all(&response.body)?;
+            Ok(())
+        })?;
+
+        Ok(())
     }
 }
 
@@ -215,10 +689,18 @@ fn index_html() -> &'static [u8] {
     include_bytes!("../../../demo-ui/dist/index.html")
 }
 
+fn index_html_gz() -> &'static [u8] {
+    include_bytes!("../../../demo-ui/dist/index.html.gz")
+}
+
 fn ui_js() -> &'static [u8] {
     include_bytes!("../../../demo-ui/dist/demo-ui.js")
 }
 
+fn ui_js_gz() -> &'static [u8] {
+    include_bytes!("../../../demo-ui/dist/demo-ui.js.gz")
+}
+
 fn ui_wasm() -> &'static [u8] {
     include_bytes!("../../../demo-ui/dist/demo-ui_bg.wasm.gz")
 }
@@ -233,3 +715,7 @@ fn dioxus_interpreter() -> &'static [u8] {
 fn ui_css() -> &'static [u8] {
     include_bytes!("../../../demo-ui/dist/style.css")
 }
+
+fn ui_css_gz() -> &'static [u8] {
+    include_bytes!("../../../demo-ui/dist/style.css.gz")
+}
@@ -1,4 +1,10 @@
 pub mod button;
+pub mod diagnostics;
 pub mod http;
+pub mod mqtt;
+pub mod openapi;
+pub mod ota;
 pub mod rgb_led;
+pub mod storage;
+pub mod webhook;
 pub mod wifi;
@@ -0,0 +1,110 @@
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+use demo::app::SystemState;
+use demo::settings::MqttSettings;
+use demo::svc::bus::Event;
+use demo::svc::log_throttle::LogThrottle;
+use demo::svc::Mqtt;
+use embedded_svc::mqtt::client::QoS;
+use esp_idf_svc::mqtt::client::{EspMqttClient, MqttClientConfiguration};
+use log::Level;
+
+// The state changes at every app tick, dashboards do not need it that often
+const STATE_PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+const LOG_THROTTLE_INTERVAL: Duration = Duration::from_secs(5);
+
+struct Connection {
+    client: RefCell<EspMqttClient>,
+    topic_prefix: String,
+}
+
+pub struct EspMqtt {
+    // None when MQTT is not configured
+    connection: Option<Connection>,
+    last_state: Cell<Option<(Instant, SystemState)>>,
+    error_log: RefCell<LogThrottle>,
+}
+
+impl EspMqtt {
+    pub fn new(settings: Option<&MqttSettings>) -> anyhow::Result<EspMqtt> {
+        let connection = match settings {
+            Some(settings) => {
+                let conf = MqttClientConfiguration::default();
+
+                // The client connects, and reconnects, in the background
+                let client = EspMqttClient::new(&settings.url, &conf, |event| {
+                    if let Err(err) = event {
+                        log::warn!("MQTT: {}", err);
+                    }
+                })?;
+
+                Some(Connection {
+                    client: RefCell::new(client),
+                    topic_prefix: settings.topic_prefix.clone(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self::with_connection(connection))
+    }
+
+    pub fn disabled() -> EspMqtt {
+        Self::with_connection(None)
+    }
+
+    fn with_connection(connection: Option<Connection>) -> EspMqtt {
+        Self {
+            connection,
+            last_state: Cell::new(None),
+            error_log: RefCell::new(LogThrottle::new(LOG_THROTTLE_INTERVAL)),
+        }
+    }
+
+    // Messages are queued, so this never waits for the network
+    fn publish(&self, subtopic: &str, retain: bool, payload: &[u8]) {
+        let Some(connection) = &self.connection else {
+            return;
+        };
+
+        let topic = format!("{}/{}", connection.topic_prefix, subtopic);
+        let mut client = connection.client.borrow_mut();
+
+        if let Err(err) = client.enqueue(&topic, QoS::AtMostOnce, retain, payload) {
+            self.error_log
+                .borrow_mut()
+                .log(Level::Error, format_args!("MQTT publish failed: {}", err));
+        }
+    }
+}
+
+impl Mqtt for EspMqtt {
+    fn publish_state(&self, state: &SystemState) {
+        let now = Instant::now();
+
+        let due = match self.last_state.get() {
+            Some((published_at, last_state)) => {
+                *state != last_state && now - published_at >= STATE_PUBLISH_INTERVAL
+            }
+            None => true,
+        };
+
+        if !due {
+            return;
+        }
+
+        self.last_state.set(Some((now, *state)));
+
+        // Retained, so dashboards get the latest state as soon as they subscribe
+        if let Ok(json) = serde_json::to_vec(state) {
+            self.publish("state", true, &json);
+        }
+    }
+
+    fn publish_event(&self, event: &Event) {
+        if let Ok(json) = serde_json::to_vec(event) {
+            self.publish("events", false, &json);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use embedded_svc::http::Method;
+use serde_json::{json, Map, Value};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Access {
+    Public,
+    // Requires the API token, when one is configured
+    Token,
+}
+
+struct ApiRoute {
+    method: Method,
+    uri: String,
+    summary: String,
+    access: Access,
+}
+
+// Description of the http API, filled while handlers are registered
+#[derive(Clone, Default)]
+pub struct ApiDoc(Arc<Mutex<Vec<ApiRoute>>>);
+
+impl ApiDoc {
+    pub fn add(&self, method: Method, uri: &str, summary: &str, access: Access) {
+        if let Ok(mut routes) = self.0.lock() {
+            routes.push(ApiRoute {
+                method,
+                uri: uri.to_owned(),
+                summary: summary.to_owned(),
+                access,
+            });
+        }
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<Vec<u8>> {
+        let routes = self.0.lock().map_err(|_| anyhow!("Cannot lock API doc"))?;
+
+        let mut paths = Map::new();
+
+        for route in routes.iter() {
+            let mut operation = json!({
+                "summary": route.summary,
+                "responses": { "200": { "description": "Success" } },
+            });
+
+            if route.access == Access::Token {
+                operation["security"] = json!([{ "token": [] }]);
+                operation["responses"]["401"] = json!({ "description": "Unauthorized" });
+            }
+
+            let method = format!("{:?}", route.method).to_lowercase();
+
+            paths.entry(route.uri.clone()).or_insert_with(|| json!({}))[method] = operation;
+        }
+
+        let doc = json!({
+            "openapi": "3.0.3",
+            "info": {
+                "title": "demo",
+                "version": env!("CARGO_PKG_VERSION"),
+            },
+            "components": {
+                "securitySchemes": {
+                    "token": { "type": "http", "scheme": "bearer" },
+                },
+            },
+            "paths": Value::Object(paths),
+        });
+
+        Ok(serde_json::to_vec(&doc)?)
+    }
+}
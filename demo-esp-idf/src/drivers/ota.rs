@@ -0,0 +1,121 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, bail};
+use demo::app::OtaStatus;
+use embedded_svc::io::Read;
+use embedded_svc::storage::RawStorage;
+use esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition};
+use esp_idf_svc::ota::{EspOta, EspOtaUpdate};
+use esp_idf_sys::esp_ota_get_last_invalid_partition;
+
+const CHUNK_SIZE: usize = 1024;
+
+const NAMESPACE: &str = "ota";
+// Set when an update is written, cleared at the next boot
+const PENDING_KEY: &str = "pending";
+
+#[derive(Clone)]
+pub struct EspOtaUpdater {
+    ota: Arc<Mutex<EspOta>>,
+    nvs: Arc<Mutex<EspDefaultNvs>>,
+}
+
+impl EspOtaUpdater {
+    pub fn new(partition: EspDefaultNvsPartition) -> anyhow::Result<EspOtaUpdater> {
+        let nvs = EspDefaultNvs::new(partition, NAMESPACE, true)?;
+
+        Ok(Self {
+            ota: Arc::new(Mutex::new(EspOta::new()?)),
+            nvs: Arc::new(Mutex::new(nvs)),
+        })
+    }
+
+    fn ota(&self) -> anyhow::Result<MutexGuard<'_, EspOta>> {
+        self.ota.lock().map_err(|_| anyhow!("Cannot lock OTA"))
+    }
+
+    fn nvs(&self) -> anyhow::Result<MutexGuard<'_, EspDefaultNvs>> {
+        self.nvs.lock().map_err(|_| anyhow!("Cannot lock NVS"))
+    }
+
+    // A new firmware boots pending verification: if it restarts before being
+    // confirmed, the bootloader goes back to the previous one
+    pub fn confirm_running_firmware(&self) -> anyhow::Result<()> {
+        self.ota()?.mark_running_slot_valid()?;
+        Ok(())
+    }
+
+    // The invalid partition stays marked until it is written again, so a
+    // rollback is only reported at the first boot after the update
+    pub fn boot_status(&self) -> OtaStatus {
+        let pending = match self.take_pending() {
+            Ok(pending) => pending,
+            Err(err) => {
+                log::warn!("Cannot read pending OTA update: {}", err);
+                false
+            }
+        };
+
+        let rolled_back = unsafe { !esp_ota_get_last_invalid_partition().is_null() };
+
+        if pending && rolled_back {
+            OtaStatus::RolledBack
+        } else {
+            OtaStatus::Idle
+        }
+    }
+
+    fn take_pending(&self) -> anyhow::Result<bool> {
+        Ok(self.nvs()?.remove(PENDING_KEY)?)
+    }
+
+    // Writes the image to the inactive partition and boots from it at the next
+    // restart. The image is verified before switching partition.
+    pub fn update(
+        &self,
+        image: impl Read,
+        size: usize,
+        mut progress: impl FnMut(usize),
+    ) -> anyhow::Result<()> {
+        let mut ota = self.ota()?;
+        let update = ota.initiate_update()?;
+
+        if let Err(err) = write_image(image, update, size, &mut progress) {
+            update.abort()?;
+            return Err(err);
+        }
+
+        update.complete()?;
+
+        let mut nvs = self.nvs()?;
+        RawStorage::set_raw(&mut *nvs, PENDING_KEY, &[1])?;
+
+        Ok(())
+    }
+}
+
+fn write_image(
+    mut image: impl Read,
+    update: &mut EspOtaUpdate,
+    size: usize,
+    progress: &mut impl FnMut(usize),
+) -> anyhow::Result<()> {
+    let mut buf = [0u8; CHUNK_SIZE];
+    let mut written = 0;
+
+    while written < size {
+        let len = image
+            .read(&mut buf)
+            .map_err(|err| anyhow!("Cannot read image: {:?}", err))?;
+
+        if len == 0 {
+            bail!("Image truncated at {} of {} bytes", written, size);
+        }
+
+        update.write(&buf[..len])?;
+        written += len;
+        progress(written);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, bail};
+use demo::hal::storage::Storage;
+use demo::settings::Settings;
+use embedded_svc::storage::RawStorage;
+use esp_idf_svc::nvs::{EspDefaultNvs, EspDefaultNvsPartition};
+
+const NAMESPACE: &str = "demo";
+const SETTINGS_KEY: &str = "settings";
+
+pub const SETTINGS_MAX_SIZE: usize = 1024;
+
+#[derive(Clone)]
+pub struct EspStorage(Arc<Mutex<EspDefaultNvs>>);
+
+impl EspStorage {
+    pub fn new(partition: EspDefaultNvsPartition) -> anyhow::Result<EspStorage> {
+        let nvs = EspDefaultNvs::new(partition, NAMESPACE, true)?;
+        Ok(Self(Arc::new(Mutex::new(nvs))))
+    }
+
+    fn nvs(&self) -> anyhow::Result<MutexGuard<'_, EspDefaultNvs>> {
+        self.0.lock().map_err(|_| anyhow!("Cannot lock NVS"))
+    }
+}
+
+impl Storage for EspStorage {
+    fn load_settings(&self) -> anyhow::Result<Option<Settings>> {
+        let nvs = self.nvs()?;
+        let mut buf = [0u8; SETTINGS_MAX_SIZE];
+
+        match nvs.get_raw(SETTINGS_KEY, &mut buf)? {
+            Some(data) => Ok(Some(serde_json::from_slice(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn store_settings(&self, settings: &Settings) -> anyhow::Result<()> {
+        let data = serde_json::to_vec(settings)?;
+
+        if data.len() > SETTINGS_MAX_SIZE {
+            bail!("Settings do not fit in {} bytes", SETTINGS_MAX_SIZE)
+        }
+
+        let mut nvs = self.nvs()?;
+        RawStorage::set_raw(&mut *nvs, SETTINGS_KEY, &data)?;
+
+        Ok(())
+    }
+
+    fn erase(&self) -> anyhow::Result<()> {
+        self.nvs()?.remove(SETTINGS_KEY)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use demo::svc::event_log::EventLogEntry;
+use demo::svc::Webhook;
+use embedded_svc::http::client::Client;
+use embedded_svc::http::Status;
+use embedded_svc::io::Write;
+use esp_idf_svc::http::client::{Configuration, EspHttpConnection};
+
+const QUEUE_SIZE: usize = 8;
+
+// Posts logged events to an http endpoint, from a dedicated thread so the
+// callers never wait for the network
+#[derive(Clone)]
+pub struct EspWebhook {
+    url: Arc<Mutex<Option<String>>>,
+    sender: SyncSender<Vec<u8>>,
+}
+
+impl EspWebhook {
+    pub fn new(url: Option<String>) -> anyhow::Result<EspWebhook> {
+        let url = Arc::new(Mutex::new(url));
+        let (sender, receiver) = sync_channel::<Vec<u8>>(QUEUE_SIZE);
+
+        let url_copy = url.clone();
+
+        std::thread::Builder::new()
+            .stack_size(8 * 1024)
+            .spawn(move || {
+                for payload in receiver {
+                    let Some(url) = url_copy.lock().ok().and_then(|x| x.clone()) else {
+                        continue;
+                    };
+
+                    if let Err(err) = post(&url, &payload) {
+                        log::warn!("Webhook failed: {}", err);
+                    }
+                }
+            })?;
+
+        Ok(Self { url, sender })
+    }
+}
+
+impl Webhook for EspWebhook {
+    fn set_url(&self, url: Option<String>) {
+        if let Ok(mut x) = self.url.lock() {
+            *x = url;
+        }
+    }
+
+    // Notifications are dropped when the queue is full
+    fn notify(&self, entry: &EventLogEntry) {
+        if matches!(self.url.lock().as_deref(), Ok(None)) {
+            return;
+        }
+
+        let Ok(payload) = serde_json::to_vec(entry) else {
+            return;
+        };
+
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(payload) {
+            log::warn!("Webhook queue full");
+        }
+    }
+}
+
+fn post(url: &str, payload: &[u8]) -> anyhow::Result<()> {
+    let connection = EspHttpConnection::new(&Configuration::default())?;
+    let mut client = Client::wrap(connection);
+
+    let content_length = payload.len().to_string();
+    let headers = [
+        ("Content-Type", "application/json"),
+        ("Content-Length", content_length.as_str()),
+    ];
+
+    let mut request = client.post(url, &headers)?;
+    request.write_all(payload)?;
+    let response = request.submit()?;
+
+    let status = response.status();
+    if !(200..300).contains(&status) {
+        bail!("Unexpected status {}", status);
+    }
+
+    Ok(())
+}
@@ -9,7 +9,7 @@ use esp_idf_hal::modem::Modem;
 use esp_idf_svc::eventloop::EspSystemEventLoop;
 use esp_idf_svc::nvs::EspDefaultNvsPartition;
 use esp_idf_svc::wifi::WifiWait;
-use demo::hal::wifi::{Wifi, WifiConfig};
+use demo::hal::wifi::{Wifi, WifiAccessPoint, WifiConfig};
 
 pub struct EspWifi {
     esp_wifi: RefCell<esp_idf_svc::wifi::EspWifi<'static>>,
@@ -53,25 +53,21 @@ fn to_esp_wifi_config(src: &WifiConfig) -> anyhow::Result<Configuration> {
 }
 
 impl EspWifi {
-    pub fn new(modem: Modem) -> anyhow::Result<EspWifi> {
+    pub fn new(modem: Modem, nvs: EspDefaultNvsPartition) -> anyhow::Result<EspWifi> {
         let sys_loop = EspSystemEventLoop::take()?;
-        let nvs = EspDefaultNvsPartition::take()?;
         let esp_wifi = esp_idf_svc::wifi::EspWifi::new(modem, sys_loop.clone(), Some(nvs))?;
         Ok(Self {
             esp_wifi: RefCell::new(esp_wifi),
             sys_loop,
         })
     }
-}
-
-impl Wifi for EspWifi {
-    fn setup(&self, config: &WifiConfig) -> anyhow::Result<()> {
-        let is_access_point = config.ap;
-        let config = to_esp_wifi_config(config)?;
 
-        let mut esp_wifi = self.esp_wifi.try_borrow_mut()?;
-
-        esp_wifi.set_configuration(&config)?;
+    fn start(
+        &self,
+        esp_wifi: &mut esp_idf_svc::wifi::EspWifi<'static>,
+        config: &Configuration,
+    ) -> anyhow::Result<()> {
+        esp_wifi.set_configuration(config)?;
         esp_wifi.start()?;
 
         let started = {
@@ -82,12 +78,20 @@ impl Wifi for EspWifi {
 
         if !started {
             log::error!("Wi-Fi did not start");
-        } else if !is_access_point {
+        } else if matches!(config, Configuration::Client(_)) {
             esp_wifi.connect()?;
         }
 
         Ok(())
     }
+}
+
+impl Wifi for EspWifi {
+    fn setup(&self, config: &WifiConfig) -> anyhow::Result<()> {
+        let config = to_esp_wifi_config(config)?;
+        let mut esp_wifi = self.esp_wifi.try_borrow_mut()?;
+        self.start(&mut esp_wifi, &config)
+    }
 
     fn is_up(&self) -> bool {
         if let Ok(esp_wifi) = self.esp_wifi.try_borrow() {
@@ -103,4 +107,28 @@ impl Wifi for EspWifi {
             esp_wifi.connect().expect("Cannot connect");
         }
     }
+
+    fn scan(&self) -> anyhow::Result<Vec<WifiAccessPoint>> {
+        let mut esp_wifi = self.esp_wifi.try_borrow_mut()?;
+
+        let config = esp_wifi.get_configuration()?;
+        let scanned = esp_wifi.scan();
+
+        // Scanning stops Wi-Fi and switches it to station mode, bring back
+        // the previous access point or connection
+        if let Err(err) = self.start(&mut esp_wifi, &config) {
+            log::error!("Cannot restore Wi-Fi after scan: {}", err);
+        }
+
+        let access_points = scanned?
+            .into_iter()
+            .map(|x| WifiAccessPoint {
+                ssid: x.ssid.as_str().to_owned(),
+                channel: x.channel,
+                signal_strength: x.signal_strength,
+            })
+            .collect();
+
+        Ok(access_points)
+    }
 }
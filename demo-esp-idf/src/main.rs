@@ -2,6 +2,7 @@ use std::time::{Duration, Instant};
 
 use demo::app::App;
 use demo::hal::wifi::WifiConfig;
+use demo::svc::log_throttle::LogThrottle;
 use esp_idf_sys as _;
 
 use demo_esp_idf::platform::{BoardType, Config, PlatformImpl};
@@ -25,6 +26,8 @@ fn main() -> anyhow::Result<()> {
 
     log::info!("Start loop");
 
+    let mut no_delay_log = LogThrottle::new(Duration::from_secs(5));
+
     loop {
         let next_wakeup = Instant::now() + TASK_WAKEUP_PERIOD;
 
@@ -38,7 +41,7 @@ fn main() -> anyhow::Result<()> {
         if let Some(delay) = next_wakeup.checked_duration_since(Instant::now()) {
             std::thread::sleep(delay);
         } else {
-            log::error!("no delay");
+            no_delay_log.log(log::Level::Error, format_args!("no delay"));
         }
     }
 }
@@ -1,14 +1,26 @@
+use std::time::Duration;
+
 use demo::hal::button::Button;
+use demo::hal::clock::{Clock, StdClock};
 use demo::hal::rgb_led::RgbLed;
+use demo::hal::storage::Storage;
 use demo::hal::wifi::{Wifi, WifiConfig};
 use demo::hal::Platform;
-use demo::svc::HttpServer;
+use demo::settings::WifiSettings;
+use demo::svc::{HttpServer, Mqtt, Webhook};
 use esp_idf_hal::gpio::InputPin;
 use esp_idf_hal::peripherals::Peripherals;
+use esp_idf_svc::nvs::EspDefaultNvsPartition;
+use esp_idf_svc::sntp::EspSntp;
 
 use crate::drivers::button::EspButton;
+use crate::drivers::diagnostics::uptime_ms;
 use crate::drivers::http::HttpServer as EspHttpServer;
+use crate::drivers::mqtt::EspMqtt;
+use crate::drivers::ota::EspOtaUpdater;
 use crate::drivers::rgb_led::WS2812RgbLed;
+use crate::drivers::storage::EspStorage;
+use crate::drivers::webhook::EspWebhook;
 use crate::drivers::wifi::EspWifi;
 
 pub enum BoardType {
@@ -20,7 +32,13 @@ pub struct PlatformImpl {
     wifi: EspWifi,
     rgb_led: WS2812RgbLed,
     button: EspButton,
+    clock: StdClock,
     http_server: EspHttpServer,
+    mqtt: EspMqtt,
+    storage: EspStorage,
+    webhook: EspWebhook,
+    #[allow(dead_code)]
+    sntp: EspSntp,
 }
 
 pub struct Config {
@@ -32,8 +50,23 @@ impl PlatformImpl {
     pub fn new(config: &Config) -> Self {
         let peripherals = Peripherals::take().unwrap();
 
-        let wifi = EspWifi::new(peripherals.modem).expect("Cannot create Wi-Fi");
-        wifi.setup(&config.wifi).expect("Cannot setup Wi-Fi");
+        let nvs = EspDefaultNvsPartition::take().expect("Cannot take NVS partition");
+        let storage = EspStorage::new(nvs.clone()).expect("Cannot setup storage");
+
+        let settings = storage
+            .load_settings()
+            .unwrap_or_else(|err| {
+                log::error!("Cannot load settings: {}", err);
+                None
+            })
+            .unwrap_or_default();
+
+        // Stored settings take precedence over the build time configuration
+        let wifi_config = settings.wifi.as_ref().map(WifiSettings::as_wifi_config);
+
+        let wifi = EspWifi::new(peripherals.modem, nvs.clone()).expect("Cannot create Wi-Fi");
+        wifi.setup(wifi_config.as_ref().unwrap_or(&config.wifi))
+            .expect("Cannot setup Wi-Fi");
 
         let rgb_led = WS2812RgbLed::default();
 
@@ -43,13 +76,37 @@ impl PlatformImpl {
         };
 
         let button = EspButton::new(button_pin).expect("Cannot setup button");
-        let http_server = EspHttpServer::new().expect("Cannot setup http server");
+        let ota = EspOtaUpdater::new(nvs).expect("Cannot setup OTA");
+        let http_server =
+            EspHttpServer::new(storage.clone(), ota.clone()).expect("Cannot setup http server");
+
+        // A broken MQTT configuration must not prevent the board from working
+        let mqtt = EspMqtt::new(settings.mqtt.as_ref()).unwrap_or_else(|err| {
+            log::error!("Cannot setup MQTT: {}", err);
+            EspMqtt::disabled()
+        });
+
+        let webhook = EspWebhook::new(settings.webhook_url).expect("Cannot setup webhook");
+
+        // Synchronizes the wall clock when the network reaches the internet
+        let sntp = EspSntp::new_default().expect("Cannot setup SNTP");
+
+        // Everything is up, so this firmware can be kept. Fails when the
+        // partition table has no OTA partitions.
+        if let Err(err) = ota.confirm_running_firmware() {
+            log::warn!("Cannot confirm running firmware: {}", err);
+        }
 
         Self {
             wifi,
             rgb_led,
             button,
+            clock: StdClock,
             http_server,
+            mqtt,
+            storage,
+            webhook,
+            sntp,
         }
     }
 }
@@ -67,7 +124,31 @@ impl Platform for PlatformImpl {
         &self.button
     }
 
+    fn clock(&self) -> &(dyn Clock + '_) {
+        &self.clock
+    }
+
     fn http_server(&self) -> &(dyn HttpServer + '_) {
         &self.http_server
     }
+
+    fn mqtt(&self) -> &(dyn Mqtt + '_) {
+        &self.mqtt
+    }
+
+    fn storage(&self) -> &(dyn Storage + '_) {
+        &self.storage
+    }
+
+    fn webhook(&self) -> &(dyn Webhook + '_) {
+        &self.webhook
+    }
+
+    fn uptime(&self) -> Duration {
+        Duration::from_millis(uptime_ms())
+    }
+
+    fn restart(&self) {
+        esp_idf_hal::reset::restart();
+    }
 }
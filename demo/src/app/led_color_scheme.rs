@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LedColorScheme {
+    pub init: u32,
+    pub idle: u32,
+    pub disconnected: u32,
+    pub error: u32,
+    pub factory_reset: u32,
+}
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum LedColorSchemeName {
+    #[default]
+    Standard,
+    // Avoids telling states apart by red and green only
+    Colorblind,
+}
+
+impl LedColorSchemeName {
+    pub fn scheme(&self) -> LedColorScheme {
+        match self {
+            LedColorSchemeName::Standard => LedColorScheme {
+                init: 0xFF0000,
+                idle: 0x00FF00,
+                disconnected: 0xFFFF00,
+                error: 0xFF0000,
+                factory_reset: 0xFFFFFF,
+            },
+            LedColorSchemeName::Colorblind => LedColorScheme {
+                init: 0xFF8000,
+                idle: 0x0000FF,
+                disconnected: 0xFFFF00,
+                error: 0xFF00FF,
+                factory_reset: 0xFFFFFF,
+            },
+        }
+    }
+}
+
+impl Default for LedColorScheme {
+    fn default() -> Self {
+        LedColorSchemeName::default().scheme()
+    }
+}
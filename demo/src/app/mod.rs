@@ -1,7 +1,27 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
 use crate::hal::button::ButtonState;
+use crate::hal::clock::Clock;
 use crate::hal::rgb_led::RgbLed;
 use crate::hal::rgb_led::RgbLedColor;
+use crate::hal::wifi::Wifi;
 use crate::hal::Platform;
+use crate::settings::Settings;
+use crate::svc::bus::{Event, EventBus, Topic};
+use crate::svc::event_log::{EventLog, EventLogEntry};
+use crate::svc::http::{HttpAccess, HttpHandler, HttpMethod, HttpResponse};
+use crate::svc::HttpServer;
+
+pub use led_color_scheme::{LedColorScheme, LedColorSchemeName};
+pub use ota_status::OtaStatus;
+
+mod led_color_scheme;
+mod ota_status;
+
+const EVENT_LOG_CAPACITY: usize = 64;
 
 #[derive(Debug, Default, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Vec3 {
@@ -14,10 +34,21 @@ pub struct Vec3 {
 pub struct SystemState {
     pub counter: u32,
     pub acc: Vec3,
+    #[serde(default)]
+    pub ota: OtaStatus,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, serde::Serialize)]
+pub enum AppPhase {
+    Init,
+    Operational,
+    FactoryReset,
+    Diagnostics,
 }
 
 struct Services<'a> {
-    led_controller: LedController<'a>,
+    bus: EventBus<'a>,
+    led_controller: Rc<LedController<'a>>,
     platform: &'a dyn Platform,
 }
 
@@ -25,6 +56,8 @@ struct Services<'a> {
 enum AppState {
     Init(InitState),
     Operational(OperationalState),
+    FactoryReset(FactoryResetState),
+    Diagnostics(DiagnosticsState),
 }
 
 impl Default for AppState {
@@ -33,75 +66,300 @@ impl Default for AppState {
     }
 }
 
+impl AppState {
+    fn phase(&self) -> AppPhase {
+        match self {
+            AppState::Init(_) => AppPhase::Init,
+            AppState::Operational(_) => AppPhase::Operational,
+            AppState::FactoryReset(_) => AppPhase::FactoryReset,
+            AppState::Diagnostics(_) => AppPhase::Diagnostics,
+        }
+    }
+}
+
 pub struct App<'a> {
     services: Services<'a>,
     state: AppState,
+    wifi_up: Option<bool>,
 }
 
 impl<'a> App<'a> {
-    pub fn new(platform: &'a mut (dyn Platform)) -> Self {
-        let led_controller = LedController {
+    pub fn new(platform: &'a mut dyn Platform) -> Self {
+        let platform: &'a dyn Platform = platform;
+
+        let led_color_scheme = platform
+            .storage()
+            .load_settings()
+            .ok()
+            .flatten()
+            .and_then(|x| x.led_color_scheme)
+            .unwrap_or_default();
+
+        let led_controller = Rc::new(LedController {
             led: platform.rgb_led(),
-        };
+            clock: platform.clock(),
+            scheme: Cell::new(led_color_scheme.scheme()),
+            last_error: Cell::new(None),
+            wifi_up: Cell::new(false),
+        });
+
+        let bus = EventBus::default();
+
+        let http_server = platform.http_server();
+        let mqtt = platform.mqtt();
+        bus.subscribe(Topic::Telemetry, move |event| {
+            if let Event::Telemetry(system_state) = event {
+                http_server.set_counter(system_state.counter);
+                http_server.set_acc(&system_state.acc);
+                mqtt.publish_state(system_state);
+            }
+        });
+
+        let led = led_controller.clone();
+        bus.subscribe(Topic::Connectivity, move |event| {
+            if let Event::Connectivity { wifi_up } = event {
+                led.wifi_up.set(*wifi_up);
+            }
+        });
+
+        let webhook = platform.webhook();
+        let event_log = Arc::new(Mutex::new(EventLog::new(EVENT_LOG_CAPACITY)));
+
+        for topic in [Topic::App, Topic::Connectivity, Topic::Settings] {
+            bus.subscribe(topic, log_event);
+
+            let event_log = event_log.clone();
+            bus.subscribe(topic, move |event| {
+                let entry = EventLogEntry::new(platform.uptime(), *event);
+                webhook.notify(&entry);
+                if let Ok(mut event_log) = event_log.lock() {
+                    event_log.push(entry);
+                }
+            });
+
+            bus.subscribe(topic, move |event| mqtt.publish_event(event));
+        }
+
+        serve_event_log(http_server, event_log);
 
         let services = Services {
+            bus,
             led_controller,
             platform,
         };
 
         let state = AppState::default();
 
-        Self { services, state }
+        Self {
+            services,
+            state,
+            wifi_up: None,
+        }
     }
 
     pub fn update(&mut self) {
         let new_state = match &mut self.state {
             AppState::Init(state) => state.update(&self.services),
             AppState::Operational(state) => state.update(&self.services),
+            AppState::FactoryReset(state) => state.update(&self.services),
+            AppState::Diagnostics(state) => state.update(&self.services),
         };
 
+        if new_state.phase() != self.state.phase() {
+            let event = Event::AppPhaseChanged(new_state.phase());
+            self.services.bus.publish(&event);
+        }
+
         if new_state != self.state {
-            // log::info!("{:?}", &new_state);
             self.state = new_state;
         }
 
+        let wifi_up = self.services.platform.wifi().is_up();
+
+        if self.wifi_up != Some(wifi_up) {
+            self.wifi_up = Some(wifi_up);
+            self.services.bus.publish(&Event::Connectivity { wifi_up });
+        }
+
+        let http_server = self.services.platform.http_server();
+
+        if let Some(settings) = http_server.take_settings_update() {
+            self.apply_settings(&settings);
+        }
+
+        for event in http_server.take_events() {
+            self.services.bus.publish(&event);
+        }
+
         self.services.led_controller.update(&self.state);
     }
+
+    fn apply_settings(&self, settings: &Settings) {
+        let scheme = settings.led_color_scheme.unwrap_or_default().scheme();
+        self.set_led_color_scheme(scheme);
+
+        let webhook_url = settings.webhook_url.clone();
+        self.services.platform.webhook().set_url(webhook_url);
+    }
+
+    pub fn set_led_color_scheme(&self, scheme: LedColorScheme) {
+        self.services.led_controller.scheme.set(scheme);
+        self.services.bus.publish(&Event::SettingsChanged);
+    }
+}
+
+fn log_event(event: &Event) {
+    log::info!("{:?}", event);
+}
+
+fn serve_event_log(http_server: &dyn HttpServer, event_log: Arc<Mutex<EventLog>>) {
+    let handler: HttpHandler = Box::new(move |_request| {
+        let json = match event_log.lock() {
+            Ok(event_log) => serde_json::to_vec(event_log.entries()),
+            Err(_) => return HttpResponse::text(500, "Cannot lock event log"),
+        };
+
+        match json {
+            Ok(json) => HttpResponse::new(200, "application/json", json),
+            Err(err) => HttpResponse::text(500, &err.to_string()),
+        }
+    });
+
+    let summary = "Latest significant events";
+    let added = http_server.add_handler(
+        HttpMethod::Get,
+        "/events",
+        HttpAccess::Public,
+        summary,
+        handler,
+    );
+
+    if let Err(err) = added {
+        log::error!("Cannot serve the event log: {}", err);
+    }
 }
 
+// Sources of LED indications, from the lowest to the highest priority
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+enum LedSource {
+    Idle,
+    Connection,
+    AppState,
+    Error,
+}
+
+const ERROR_INDICATION_TIME: Duration = Duration::from_secs(3);
+const ERROR_BLINK_PERIOD: Duration = Duration::from_millis(250);
+
 struct LedController<'a> {
     led: &'a dyn RgbLed,
+    clock: &'a dyn Clock,
+    scheme: Cell<LedColorScheme>,
+    last_error: Cell<Option<Instant>>,
+    wifi_up: Cell<bool>,
 }
 
 impl<'a> LedController<'a> {
-    pub fn update(&mut self, app_state: &AppState) {
-        let color = color_from_app_state(app_state);
+    pub fn update(&self, app_state: &AppState) {
+        let scheme = self.scheme.get();
+
+        let indications = [
+            (LedSource::Error, self.error_color(&scheme)),
+            (
+                LedSource::AppState,
+                color_from_app_state(app_state, &scheme),
+            ),
+            (LedSource::Connection, self.connection_color(&scheme)),
+            (LedSource::Idle, Some(scheme.idle)),
+        ];
+
+        let color = arbitrate(&indications);
         self.led.set_color(RgbLedColor::from(color));
     }
+
+    pub fn indicate_error(&self) {
+        self.last_error.set(Some(self.clock.now()));
+    }
+
+    fn error_color(&self, scheme: &LedColorScheme) -> Option<u32> {
+        let elapsed = self.clock.now() - self.last_error.get()?;
+
+        if elapsed >= ERROR_INDICATION_TIME {
+            return None;
+        }
+
+        let blink_period = ERROR_BLINK_PERIOD.as_millis();
+        let blink_on = elapsed.as_millis() % (2 * blink_period) < blink_period;
+
+        Some(if blink_on { scheme.error } else { OFF })
+    }
+
+    fn connection_color(&self, scheme: &LedColorScheme) -> Option<u32> {
+        if self.wifi_up.get() {
+            None
+        } else {
+            Some(scheme.disconnected)
+        }
+    }
+}
+
+// The active indication with the highest priority wins
+fn arbitrate(indications: &[(LedSource, Option<u32>)]) -> u32 {
+    indications
+        .iter()
+        .filter_map(|&(source, color)| Some((source, color?)))
+        .max_by_key(|&(source, _)| source)
+        .map(|(_, color)| color)
+        .unwrap_or(OFF)
 }
 
-fn color_from_app_state(app_state: &AppState) -> u32 {
-    const RED: u32 = 0xFF0000;
-    const YELLOW: u32 = 0xFFFF00;
-    const GREEN: u32 = 0x00FF00;
-    const BLUE: u32 = 0x0000FF;
-    const WHITE: u32 = 0xFFFFFF;
+const OFF: u32 = 0x000000;
 
+fn color_from_app_state(app_state: &AppState, scheme: &LedColorScheme) -> Option<u32> {
     match app_state {
-        AppState::Init(_) => RED,
-        AppState::Operational(_) => GREEN,
+        AppState::Init(_) => Some(scheme.init),
+        AppState::Operational(_) => None,
+        AppState::FactoryReset(_) => Some(scheme.factory_reset),
+        AppState::Diagnostics(state) => Some(state.led_color()),
     }
 }
 
+// Releasing the button after keeping it pressed at boot for this long enters
+// diagnostics
+const DIAGNOSTICS_HOLD_TIME: Duration = Duration::from_secs(3);
+
+// Keeping the button pressed at boot for this long wipes the stored configuration
+const FACTORY_RESET_HOLD_TIME: Duration = Duration::from_secs(10);
+
 #[derive(Default, Copy, Clone, Eq, PartialEq, Debug)]
 struct InitState {
     button_state: ButtonState,
+    pressed_since: Option<Instant>,
 }
 
 impl InitState {
     pub fn update(&mut self, services: &Services) -> AppState {
+        let now = services.platform.clock().now();
+
         if services.platform.button().is_pressed() {
-            AppState::Init(*self)
+            let pressed_since = *self.pressed_since.get_or_insert(now);
+
+            if now - pressed_since < FACTORY_RESET_HOLD_TIME {
+                return AppState::Init(*self);
+            }
+
+            log::warn!("Factory reset");
+
+            if let Err(err) = services.platform.storage().erase() {
+                log::error!("Factory reset failed: {}", err);
+                services.led_controller.indicate_error();
+                self.pressed_since = None;
+                return AppState::Init(*self);
+            }
+
+            AppState::FactoryReset(FactoryResetState)
+        } else if matches!(self.pressed_since, Some(t) if now - t >= DIAGNOSTICS_HOLD_TIME) {
+            AppState::Diagnostics(DiagnosticsState::new(services))
         } else {
             AppState::Operational(OperationalState {
                 system_state: Default::default(),
@@ -125,11 +383,331 @@ impl OperationalState {
             self.system_state.counter = 0;
         }
 
-        services
-            .platform
-            .http_server()
-            .set_system_state(&self.system_state);
+        self.system_state.ota = services.platform.http_server().ota_status();
+
+        services.bus.publish(&Event::Telemetry(self.system_state));
 
         AppState::Operational(*self)
     }
 }
+
+#[derive(Default, Copy, Clone, Eq, PartialEq, Debug)]
+struct FactoryResetState;
+
+impl FactoryResetState {
+    pub fn update(&mut self, services: &Services) -> AppState {
+        // Restart when the button is released, so defaults are loaded at boot
+        if !services.platform.button().is_pressed() {
+            log::info!("Restart");
+            services.platform.restart();
+        }
+
+        AppState::FactoryReset(*self)
+    }
+}
+
+// Primary colors and white, independent of the color scheme, to check every
+// channel of the LED
+const DIAGNOSTICS_LED_COLORS: [u32; 5] = [0xFF0000, 0xFFFF00, 0x00FF00, 0x0000FF, 0xFFFFFF];
+const DIAGNOSTICS_STEP_PERIOD: Duration = Duration::from_millis(500);
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct DiagnosticsState {
+    step: usize,
+    step_started: Instant,
+}
+
+impl DiagnosticsState {
+    fn new(services: &Services) -> Self {
+        log::info!("Enter diagnostics");
+
+        if !log_wifi_scan(services.platform.wifi()) {
+            services.led_controller.indicate_error();
+        }
+
+        Self {
+            step: 0,
+            step_started: services.platform.clock().now(),
+        }
+    }
+
+    pub fn update(&mut self, services: &Services) -> AppState {
+        let button = services.platform.button();
+
+        // The button is released when diagnostics starts, pressing it again
+        // goes back to normal operation
+        if button.is_pressed() {
+            log::info!("Leave diagnostics");
+            return AppState::Init(InitState::default());
+        }
+
+        let now = services.platform.clock().now();
+
+        if now - self.step_started >= DIAGNOSTICS_STEP_PERIOD {
+            self.step = (self.step + 1) % DIAGNOSTICS_LED_COLORS.len();
+            self.step_started = now;
+
+            // Report once per LED cycle
+            if self.step == 0 {
+                log::info!(
+                    "button: {:?}, wifi up: {}",
+                    button.state(),
+                    services.platform.wifi().is_up()
+                );
+            }
+        }
+
+        AppState::Diagnostics(*self)
+    }
+
+    fn led_color(&self) -> u32 {
+        DIAGNOSTICS_LED_COLORS[self.step]
+    }
+}
+
+fn log_wifi_scan(wifi: &dyn Wifi) -> bool {
+    match wifi.scan() {
+        Ok(access_points) => {
+            log::info!("Wi-Fi scan found {} access points", access_points.len());
+            for ap in access_points {
+                log::info!(
+                    "{} (channel {}, {} dBm)",
+                    ap.ssid,
+                    ap.channel,
+                    ap.signal_strength
+                );
+            }
+            true
+        }
+        Err(err) => {
+            log::error!("Wi-Fi scan failed: {}", err);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::button::Button;
+    use crate::hal::clock::ManualClock;
+    use crate::hal::storage::Storage;
+    use crate::hal::wifi::{WifiAccessPoint, WifiConfig};
+    use crate::svc::{Mqtt, Webhook};
+
+    #[derive(Default)]
+    struct Fakes {
+        button: FakeButton,
+        clock: ManualClock,
+        services: FakeServices,
+        led: FakeLed,
+        storage: FakeStorage,
+        wifi: FakeWifi,
+        restarted: Cell<bool>,
+    }
+
+    #[derive(Default)]
+    struct FakeButton(Cell<ButtonState>);
+
+    #[derive(Default)]
+    struct FakeServices;
+
+    #[derive(Default)]
+    struct FakeLed(Cell<u32>);
+
+    #[derive(Default)]
+    struct FakeStorage {
+        erased: Cell<bool>,
+    }
+
+    #[derive(Default)]
+    struct FakeWifi;
+
+    // The app borrows the platform mutably, so it is implemented for a shared
+    // reference and the fakes stay reachable from the test
+    impl Platform for &Fakes {
+        fn button(&self) -> &(dyn Button + '_) {
+            &self.button
+        }
+
+        fn clock(&self) -> &(dyn Clock + '_) {
+            &self.clock
+        }
+
+        fn http_server(&self) -> &(dyn HttpServer + '_) {
+            &self.services
+        }
+
+        fn mqtt(&self) -> &(dyn Mqtt + '_) {
+            &self.services
+        }
+
+        fn rgb_led(&self) -> &(dyn RgbLed + '_) {
+            &self.led
+        }
+
+        fn storage(&self) -> &(dyn Storage + '_) {
+            &self.storage
+        }
+
+        fn wifi(&self) -> &(dyn Wifi + '_) {
+            &self.wifi
+        }
+
+        fn webhook(&self) -> &(dyn Webhook + '_) {
+            &self.services
+        }
+
+        fn uptime(&self) -> Duration {
+            Duration::ZERO
+        }
+
+        fn restart(&self) {
+            self.restarted.set(true);
+        }
+    }
+
+    impl Button for FakeButton {
+        fn state(&self) -> ButtonState {
+            self.0.get()
+        }
+    }
+
+    impl HttpServer for FakeServices {
+        fn set_counter(&self, _counter: u32) {}
+
+        fn set_acc(&self, _acc: &Vec3) {}
+
+        fn ota_status(&self) -> OtaStatus {
+            OtaStatus::default()
+        }
+
+        fn take_settings_update(&self) -> Option<Settings> {
+            None
+        }
+
+        fn take_events(&self) -> Vec<Event> {
+            Vec::new()
+        }
+
+        fn add_handler(
+            &self,
+            _method: HttpMethod,
+            _uri: &str,
+            _access: HttpAccess,
+            _summary: &str,
+            _handler: HttpHandler,
+        ) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Mqtt for FakeServices {
+        fn publish_state(&self, _state: &SystemState) {}
+
+        fn publish_event(&self, _event: &Event) {}
+    }
+
+    impl Webhook for FakeServices {
+        fn set_url(&self, _url: Option<String>) {}
+
+        fn notify(&self, _entry: &EventLogEntry) {}
+    }
+
+    impl RgbLed for FakeLed {
+        fn set_color(&self, color: RgbLedColor) {
+            let RgbLedColor { r, g, b } = color;
+            self.0.set(u32::from_be_bytes([0, r, g, b]));
+        }
+    }
+
+    impl Storage for FakeStorage {
+        fn load_settings(&self) -> anyhow::Result<Option<Settings>> {
+            Ok(None)
+        }
+
+        fn store_settings(&self, _settings: &Settings) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn erase(&self) -> anyhow::Result<()> {
+            self.erased.set(true);
+            Ok(())
+        }
+    }
+
+    impl Wifi for FakeWifi {
+        fn setup(&self, _config: &WifiConfig) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        fn is_up(&self) -> bool {
+            true
+        }
+
+        fn reconnect(&self) {}
+
+        fn scan(&self) -> anyhow::Result<Vec<WifiAccessPoint>> {
+            Ok(Vec::new())
+        }
+    }
+
+    // Keeps the button pressed at boot for the given time, then releases it
+    fn boot_holding_button(fakes: &Fakes, hold_time: Duration) -> AppPhase {
+        let mut platform = fakes;
+        let mut app = App::new(&mut platform);
+
+        fakes.button.0.set(ButtonState::Pressed);
+        app.update();
+        fakes.clock.advance(hold_time);
+        app.update();
+        fakes.button.0.set(ButtonState::Released);
+        app.update();
+
+        app.state.phase()
+    }
+
+    #[test]
+    fn short_press_at_boot_starts_operational() {
+        let fakes = Fakes::default();
+        let phase = boot_holding_button(&fakes, Duration::from_millis(2_900));
+        assert_eq!(phase, AppPhase::Operational);
+    }
+
+    #[test]
+    fn holding_button_for_3s_at_boot_enters_diagnostics() {
+        let fakes = Fakes::default();
+        let phase = boot_holding_button(&fakes, Duration::from_secs(3));
+        assert_eq!(phase, AppPhase::Diagnostics);
+        assert_eq!(fakes.led.0.get(), DIAGNOSTICS_LED_COLORS[0]);
+        assert!(!fakes.storage.erased.get());
+    }
+
+    #[test]
+    fn holding_button_for_10s_at_boot_resets_and_restarts() {
+        let fakes = Fakes::default();
+        let phase = boot_holding_button(&fakes, Duration::from_secs(10));
+        assert_eq!(phase, AppPhase::FactoryReset);
+        assert!(fakes.storage.erased.get());
+        assert!(fakes.restarted.get());
+    }
+
+    #[test]
+    fn highest_priority_active_indication_wins() {
+        let all = [
+            (LedSource::Error, Some(1)),
+            (LedSource::AppState, Some(2)),
+            (LedSource::Connection, Some(3)),
+            (LedSource::Idle, Some(4)),
+        ];
+        assert_eq!(arbitrate(&all), 1);
+        assert_eq!(arbitrate(&all[1..]), 2);
+        assert_eq!(arbitrate(&all[2..]), 3);
+        assert_eq!(arbitrate(&all[3..]), 4);
+
+        let inactive_error = [(LedSource::Error, None), (LedSource::Idle, Some(4))];
+        assert_eq!(arbitrate(&inactive_error), 4);
+
+        assert_eq!(arbitrate(&[]), OFF);
+    }
+}
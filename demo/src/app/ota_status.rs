@@ -0,0 +1,18 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OtaStatus {
+    #[default]
+    Idle,
+    // Bytes of the firmware image written so far
+    InProgress {
+        written: u32,
+        size: u32,
+    },
+    // The new firmware runs after the restart
+    Completed,
+    Failed,
+    // The last update did not confirm itself and the bootloader went back to
+    // the running firmware
+    RolledBack,
+}
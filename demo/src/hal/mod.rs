@@ -1,15 +1,28 @@
+use std::time::Duration;
+
 use crate::hal::button::Button;
+use crate::hal::clock::Clock;
 use crate::hal::rgb_led::RgbLed;
+use crate::hal::storage::Storage;
 use crate::hal::wifi::Wifi;
-use crate::svc::HttpServer;
+use crate::svc::{HttpServer, Mqtt, Webhook};
 
 pub mod button;
+pub mod clock;
 pub mod rgb_led;
+pub mod storage;
 pub mod wifi;
 
 pub trait Platform {
     fn button(&self) -> &(dyn Button + '_);
+    fn clock(&self) -> &(dyn Clock + '_);
     fn http_server(&self) -> &(dyn HttpServer + '_);
+    fn mqtt(&self) -> &(dyn Mqtt + '_);
     fn rgb_led(&self) -> &(dyn RgbLed + '_);
+    fn storage(&self) -> &(dyn Storage + '_);
     fn wifi(&self) -> &(dyn Wifi + '_);
+    fn webhook(&self) -> &(dyn Webhook + '_);
+    // Time since boot, which starts before the platform is set up
+    fn uptime(&self) -> Duration;
+    fn restart(&self);
 }
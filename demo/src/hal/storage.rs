@@ -0,0 +1,9 @@
+use crate::settings::Settings;
+
+pub trait Storage {
+    fn load_settings(&self) -> anyhow::Result<Option<Settings>>;
+
+    fn store_settings(&self, settings: &Settings) -> anyhow::Result<()>;
+
+    fn erase(&self) -> anyhow::Result<()>;
+}
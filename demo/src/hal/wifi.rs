@@ -4,6 +4,15 @@ pub trait Wifi {
     fn is_up(&self) -> bool;
 
     fn reconnect(&self);
+
+    fn scan(&self) -> anyhow::Result<Vec<WifiAccessPoint>>;
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WifiAccessPoint {
+    pub ssid: String,
+    pub channel: u8,
+    pub signal_strength: i8,
 }
 
 #[derive(Eq, PartialEq)]
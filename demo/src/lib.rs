@@ -1,6 +1,4 @@
-use serde::Deserialize;
-use serde::Serialize;
-
 pub mod app;
 pub mod hal;
+pub mod settings;
 pub mod svc;
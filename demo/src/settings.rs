@@ -0,0 +1,253 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::app::LedColorSchemeName;
+use crate::hal::wifi::WifiConfig;
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub wifi: Option<WifiSettings>,
+    pub led_color_scheme: Option<LedColorSchemeName>,
+    // Required by the http endpoints changing or exposing settings
+    pub api_token: Option<String>,
+    pub state_push: Option<StatePushSettings>,
+    // Receives logged events
+    pub webhook_url: Option<String>,
+    pub mqtt: Option<MqttSettings>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WifiSettings {
+    pub ap: bool,
+    pub ssid: String,
+    pub password: String,
+}
+
+// How the state is pushed to WebSocket clients
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StatePushSettings {
+    pub interval_ms: u32,
+    // Skip pushes when the state did not change since the previous one
+    pub only_on_change: bool,
+}
+
+impl Default for StatePushSettings {
+    fn default() -> Self {
+        Self {
+            interval_ms: 250,
+            only_on_change: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MqttSettings {
+    pub url: String,
+    // State and events are published under this topic
+    pub topic_prefix: String,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum SettingsError {
+    InvalidSsid,
+    InvalidPassword,
+    InvalidApiToken,
+    InvalidStatePushInterval,
+    InvalidWebhookUrl,
+    InvalidMqttUrl,
+    InvalidMqttTopic,
+}
+
+impl Settings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if let Some(wifi) = &self.wifi {
+            wifi.validate()?;
+        }
+
+        if let Some(token) = &self.api_token {
+            // Sent in a header, so it must be printable
+            let printable = token.bytes().all(|c| c.is_ascii_graphic());
+            if token.is_empty() || token.len() > 64 || !printable {
+                return Err(SettingsError::InvalidApiToken);
+            }
+        }
+
+        if let Some(state_push) = &self.state_push {
+            state_push.validate()?;
+        }
+
+        // https would need the certificate bundle, which is disabled
+        if let Some(url) = &self.webhook_url {
+            if !url.starts_with("http://") || url.len() > 128 || url.contains(char::is_control) {
+                return Err(SettingsError::InvalidWebhookUrl);
+            }
+        }
+
+        if let Some(mqtt) = &self.mqtt {
+            mqtt.validate()?;
+        }
+
+        Ok(())
+    }
+
+    // Without an API token every client is authorized
+    pub fn is_authorized(&self, token: Option<&str>) -> bool {
+        match &self.api_token {
+            Some(api_token) => token == Some(api_token.as_str()),
+            None => true,
+        }
+    }
+}
+
+impl StatePushSettings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if !(20..=10_000).contains(&self.interval_ms) {
+            return Err(SettingsError::InvalidStatePushInterval);
+        }
+
+        Ok(())
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms as u64)
+    }
+}
+
+impl MqttSettings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        // Both end up in C strings, which cannot hold a NUL
+        let url = &self.url;
+        if !url.starts_with("mqtt://") || url.len() > 128 || url.contains(char::is_control) {
+            return Err(SettingsError::InvalidMqttUrl);
+        }
+
+        // Wildcards are only allowed when subscribing
+        let prefix = &self.topic_prefix;
+        if prefix.is_empty()
+            || prefix.len() > 64
+            || prefix.contains(['+', '#'])
+            || prefix.contains(char::is_control)
+        {
+            return Err(SettingsError::InvalidMqttTopic);
+        }
+
+        Ok(())
+    }
+}
+
+impl WifiSettings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.ssid.is_empty() || self.ssid.len() > 32 {
+            return Err(SettingsError::InvalidSsid);
+        }
+
+        // An empty password disables authentication, otherwise WPA2 requires
+        // between 8 and 64 characters
+        if !self.password.is_empty() && !(8..=64).contains(&self.password.len()) {
+            return Err(SettingsError::InvalidPassword);
+        }
+
+        Ok(())
+    }
+
+    pub fn as_wifi_config(&self) -> WifiConfig<'_> {
+        WifiConfig {
+            ap: self.ap,
+            ssid: &self.ssid,
+            password: &self.password,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_token(token: &str) -> Settings {
+        Settings {
+            api_token: Some(token.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn every_client_is_authorized_without_a_token() {
+        let settings = Settings::default();
+        assert!(settings.is_authorized(None));
+        assert!(settings.is_authorized(Some("anything")));
+    }
+
+    #[test]
+    fn only_the_configured_token_is_authorized() {
+        let settings = with_token("secret");
+        assert!(settings.is_authorized(Some("secret")));
+        assert!(!settings.is_authorized(Some("wrong")));
+        assert!(!settings.is_authorized(None));
+    }
+
+    #[test]
+    fn invalid_api_tokens_are_rejected() {
+        let too_long = "x".repeat(65);
+
+        for token in ["", "with space", "tab\t", too_long.as_str()] {
+            assert_eq!(
+                with_token(token).validate(),
+                Err(SettingsError::InvalidApiToken)
+            );
+        }
+
+        assert_eq!(with_token(&"x".repeat(64)).validate(), Ok(()));
+    }
+
+    fn mqtt(url: &str, topic_prefix: &str) -> MqttSettings {
+        MqttSettings {
+            url: url.to_owned(),
+            topic_prefix: topic_prefix.to_owned(),
+        }
+    }
+
+    #[test]
+    fn invalid_mqtt_settings_are_rejected() {
+        let invalid_url = SettingsError::InvalidMqttUrl;
+        let invalid_topic = SettingsError::InvalidMqttTopic;
+
+        assert_eq!(mqtt("mqtt://broker", "demo").validate(), Ok(()));
+        assert_eq!(mqtt("http://broker", "demo").validate(), Err(invalid_url));
+        assert_eq!(mqtt("mqtt://broker\n", "demo").validate(), Err(invalid_url));
+        assert_eq!(
+            mqtt("mqtt://broker", "demo/+").validate(),
+            Err(invalid_topic)
+        );
+        assert_eq!(
+            mqtt("mqtt://broker", "demo/#").validate(),
+            Err(invalid_topic)
+        );
+        assert_eq!(mqtt("mqtt://broker", "").validate(), Err(invalid_topic));
+    }
+
+    fn with_webhook(url: &str) -> Settings {
+        Settings {
+            webhook_url: Some(url.to_owned()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn invalid_webhook_urls_are_rejected() {
+        let too_long = format!("http://{}", "x".repeat(122));
+
+        for url in [
+            "https://example.com",
+            "http://example.com\0",
+            too_long.as_str(),
+        ] {
+            assert_eq!(
+                with_webhook(url).validate(),
+                Err(SettingsError::InvalidWebhookUrl)
+            );
+        }
+
+        assert_eq!(with_webhook("http://example.com").validate(), Ok(()));
+    }
+}
@@ -0,0 +1,89 @@
+use std::cell::RefCell;
+
+use serde::Serialize;
+
+use crate::app::{AppPhase, SystemState};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Topic {
+    App,
+    Connectivity,
+    Settings,
+    Telemetry,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize)]
+pub enum Event {
+    AppPhaseChanged(AppPhase),
+    Connectivity { wifi_up: bool },
+    SettingsChanged,
+    Telemetry(SystemState),
+}
+
+impl Event {
+    pub fn topic(&self) -> Topic {
+        match self {
+            Event::AppPhaseChanged(_) => Topic::App,
+            Event::Connectivity { .. } => Topic::Connectivity,
+            Event::SettingsChanged => Topic::Settings,
+            Event::Telemetry(_) => Topic::Telemetry,
+        }
+    }
+}
+
+type Subscriber<'a> = Box<dyn Fn(&Event) + 'a>;
+
+#[derive(Default)]
+pub struct EventBus<'a> {
+    subscribers: RefCell<Vec<(Topic, Subscriber<'a>)>>,
+}
+
+impl<'a> EventBus<'a> {
+    // Panics when called from a subscriber, while an event is published
+    pub fn subscribe(&self, topic: Topic, subscriber: impl Fn(&Event) + 'a) {
+        self.subscribers
+            .borrow_mut()
+            .push((topic, Box::new(subscriber)));
+    }
+
+    pub fn publish(&self, event: &Event) {
+        let topic = event.topic();
+
+        for (_, subscriber) in self.subscribers.borrow().iter().filter(|x| x.0 == topic) {
+            subscriber(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_only_reach_subscribers_of_their_topic() {
+        let received = RefCell::new(Vec::new());
+        let bus = EventBus::default();
+
+        bus.subscribe(Topic::Settings, |event| received.borrow_mut().push(*event));
+        bus.subscribe(Topic::Connectivity, |_| panic!("wrong topic"));
+
+        bus.publish(&Event::SettingsChanged);
+
+        assert_eq!(*received.borrow(), [Event::SettingsChanged]);
+    }
+
+    #[test]
+    fn subscribers_run_in_subscription_order() {
+        let order = RefCell::new(Vec::new());
+        let bus = EventBus::default();
+
+        for id in 0..3 {
+            let order = &order;
+            bus.subscribe(Topic::App, move |_| order.borrow_mut().push(id));
+        }
+
+        bus.publish(&Event::AppPhaseChanged(AppPhase::Init));
+
+        assert_eq!(*order.borrow(), [0, 1, 2]);
+    }
+}
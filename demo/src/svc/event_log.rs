@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+use crate::svc::bus::Event;
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize)]
+pub struct EventLogEntry {
+    pub uptime_ms: u64,
+    // Milliseconds since the Unix epoch, only when the wall clock is known
+    pub wall_time_ms: Option<u64>,
+    pub event: Event,
+}
+
+impl EventLogEntry {
+    pub fn new(uptime: Duration, event: Event) -> Self {
+        Self {
+            uptime_ms: uptime.as_millis() as u64,
+            wall_time_ms: wall_time_ms(),
+            event,
+        }
+    }
+}
+
+// Until SNTP synchronizes, the system time counts from the epoch at boot
+fn wall_time_ms() -> Option<u64> {
+    const MIN_VALID_TIME: Duration = Duration::from_secs(1_672_531_200); // 2023-01-01
+
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).ok()?;
+    (since_epoch >= MIN_VALID_TIME).then_some(since_epoch.as_millis() as u64)
+}
+
+// Keeps the latest events, the oldest ones are dropped when full
+pub struct EventLog {
+    entries: VecDeque<EventLogEntry>,
+    capacity: usize,
+}
+
+impl EventLog {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Event log capacity must be non-zero");
+
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, entry: EventLogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(entry);
+    }
+
+    pub fn entries(&self) -> &VecDeque<EventLogEntry> {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::AppPhase;
+
+    fn entry(uptime_ms: u64) -> EventLogEntry {
+        EventLogEntry {
+            uptime_ms,
+            wall_time_ms: None,
+            event: Event::AppPhaseChanged(AppPhase::Operational),
+        }
+    }
+
+    #[test]
+    fn oldest_entry_is_dropped_when_full() {
+        let mut event_log = EventLog::new(2);
+
+        event_log.push(entry(1));
+        event_log.push(entry(2));
+        event_log.push(entry(3));
+
+        let uptimes: Vec<u64> = event_log.entries().iter().map(|x| x.uptime_ms).collect();
+        assert_eq!(uptimes, [2, 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_capacity_is_rejected() {
+        EventLog::new(0);
+    }
+}
@@ -0,0 +1,41 @@
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum HttpAccess {
+    Public,
+    // The API token is checked before the handler runs
+    Token,
+}
+
+pub struct HttpRequest<'a> {
+    pub body: &'a [u8],
+}
+
+pub struct HttpResponse {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn text(status: u16, text: &str) -> Self {
+        Self::new(status, "text/plain", text.as_bytes().to_vec())
+    }
+}
+
+// Handlers run on the http server task, not on the app one
+pub type HttpHandler = Box<dyn Fn(&HttpRequest) -> HttpResponse + Send + 'static>;
@@ -0,0 +1,78 @@
+use std::fmt::Arguments;
+use std::time::{Duration, Instant};
+
+use log::Level;
+
+use crate::hal::clock::{Clock, StdClock};
+
+// Logs a message at most once per interval, reporting how many times it has
+// been repeated in the meantime
+pub struct LogThrottle<C = StdClock> {
+    clock: C,
+    interval: Duration,
+    last_logged: Option<Instant>,
+    repeated: u32,
+}
+
+impl LogThrottle {
+    pub fn new(interval: Duration) -> Self {
+        Self::with_clock(interval, StdClock)
+    }
+}
+
+impl<C: Clock> LogThrottle<C> {
+    pub fn with_clock(interval: Duration, clock: C) -> Self {
+        Self {
+            clock,
+            interval,
+            last_logged: None,
+            repeated: 0,
+        }
+    }
+
+    pub fn log(&mut self, level: Level, args: Arguments) {
+        let now = self.clock.now();
+
+        if let Some(last_logged) = self.last_logged {
+            if now - last_logged < self.interval {
+                self.repeated += 1;
+                return;
+            }
+        }
+
+        if self.repeated > 0 {
+            log::log!(level, "{} (repeated {} times)", args, self.repeated);
+        } else {
+            log::log!(level, "{}", args);
+        }
+
+        self.last_logged = Some(now);
+        self.repeated = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hal::clock::ManualClock;
+
+    #[test]
+    fn repeated_messages_are_counted_until_the_interval_elapses() {
+        let clock = ManualClock::default();
+        let mut throttle = LogThrottle::with_clock(Duration::from_secs(5), &clock);
+
+        throttle.log(Level::Info, format_args!("message"));
+        assert_eq!(throttle.last_logged, Some(clock.now()));
+
+        clock.advance(Duration::from_secs(2));
+        throttle.log(Level::Info, format_args!("message"));
+        clock.advance(Duration::from_secs(2));
+        throttle.log(Level::Info, format_args!("message"));
+        assert_eq!(throttle.repeated, 2);
+
+        clock.advance(Duration::from_secs(1));
+        throttle.log(Level::Info, format_args!("message"));
+        assert_eq!(throttle.repeated, 0);
+        assert_eq!(throttle.last_logged, Some(clock.now()));
+    }
+}
@@ -1,5 +1,49 @@
-use crate::app::SystemState;
+use crate::app::{OtaStatus, SystemState, Vec3};
+use crate::settings::Settings;
+use crate::svc::bus::Event;
+use crate::svc::event_log::EventLogEntry;
+use crate::svc::http::{HttpAccess, HttpHandler, HttpMethod};
+
+pub mod bus;
+pub mod event_log;
+pub mod http;
+pub mod log_throttle;
 
 pub trait HttpServer {
-    fn set_system_state(&self, status: &SystemState);
+    fn set_counter(&self, counter: u32);
+
+    fn set_acc(&self, acc: &Vec3);
+
+    // Firmware updates are uploaded to the server, which tracks their status
+    fn ota_status(&self) -> OtaStatus;
+
+    // Settings received by the server since the last call, to be applied at
+    // runtime
+    fn take_settings_update(&self) -> Option<Settings>;
+
+    // Events originated by the server since the last call, to be published
+    // on the bus
+    fn take_events(&self) -> Vec<Event>;
+
+    fn add_handler(
+        &self,
+        method: HttpMethod,
+        uri: &str,
+        access: HttpAccess,
+        summary: &str,
+        handler: HttpHandler,
+    ) -> anyhow::Result<()>;
+}
+
+pub trait Webhook {
+    fn set_url(&self, url: Option<String>);
+
+    // Must not wait for the network
+    fn notify(&self, entry: &EventLogEntry);
+}
+
+pub trait Mqtt {
+    fn publish_state(&self, state: &SystemState);
+
+    fn publish_event(&self, event: &Event);
 }